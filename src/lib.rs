@@ -4,8 +4,12 @@ use event_system::{
     PlayerId, PluginError, Position, ServerContext, SimplePlugin,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 // ============================================================================
@@ -18,22 +22,94 @@ pub struct SamplePlugin {
     // Plugin state - using Mutex for thread-safe access
     player_data: Arc<Mutex<HashMap<PlayerId, PlayerData>>>,
     config: PluginConfig,
+    // Time source for time-based statistics (swappable for tests)
+    clock: Arc<dyn Clock>,
+    // Sampled player counts for the concurrency moving average
+    concurrency: Arc<Mutex<ConcurrencyWindow>>,
+    // Account ids reported on connect, used to reconcile reconnecting players.
+    // Always lock `player_data` before `account_of`.
+    account_of: Arc<Mutex<HashMap<PlayerId, String>>>,
+    // Completed sessions per player, most recent last
+    session_history: Arc<Mutex<HashMap<PlayerId, VecDeque<SessionRecord>>>>,
+    // Most recent jump heights across all players, for the height histogram
+    jump_heights: Arc<Mutex<VecDeque<f64>>>,
+    // Distinct players who have spoken in each chat channel
+    channel_speakers: Arc<Mutex<HashMap<String, HashSet<PlayerId>>>>,
+    // Chat messages seen per channel
+    channel_messages: Arc<Mutex<HashMap<String, u64>>>,
+    metrics: Arc<PluginMetrics>,
+    // Event system handed to `register_handlers`, for emits outside of handlers
+    events: Option<Arc<EventSystem>>,
+    // Optional state persistence; repeated failures flip the plugin into read-only mode
+    persistence: Option<Arc<StatePersistence>>,
+    read_only: Arc<AtomicBool>,
+    flush_task: Option<JoinHandle<()>>,
+    sampler_task: Option<JoinHandle<()>>,
+    // Event keys this instance has already registered handlers for, and the
+    // address of the event system they were registered on
+    registered_events: HashSet<String>,
+    registered_on: Option<usize>,
 }
 
 /// Configuration for the plugin
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginConfig {
     pub welcome_message: String,
+    /// Sent to a player as they disconnect; `{player}` is replaced with their id
+    pub goodbye_message: String,
     pub max_players_tracked: usize,
     pub enable_notifications: bool,
+    /// Seconds between player count samples
+    pub sample_interval_secs: u64,
+    /// Number of samples kept for the concurrency moving average
+    pub concurrency_window: usize,
+    /// Largest serialized payload accepted by raw JSON handlers
+    pub max_ingest_bytes: usize,
+    /// Sort player-keyed batch payloads by player id so they serialize identically
+    pub deterministic_batch_order: bool,
+    /// Players allowed to look up other players with `!stats <player>`
+    pub admin_players: Vec<PlayerId>,
+    /// Whether `!stats <player>` lookups are honoured; when off the target is ignored
+    pub allow_admin_stats_lookup: bool,
+    /// Completed sessions remembered per player
+    pub max_sessions_per_player: usize,
+    /// Player ids kept in the session history; the least recently seen are evicted first
+    pub max_session_history_players: usize,
+    /// Skip handlers that were already registered (e.g. on hot reload) instead of doubling them up
+    pub guard_duplicate_registration: bool,
+    /// Jump heights retained for the histogram
+    pub max_jump_samples: usize,
+    /// Emit `sample::player_count_changed` whenever the tracked player count changes
+    pub emit_player_count_changes: bool,
+    /// Seconds between periodic state flushes (when a state writer is configured)
+    pub flush_interval_secs: u64,
+    /// Consecutive flush failures before the plugin degrades to read-only
+    pub max_flush_failures: u32,
+    /// Fraction of position updates applied (1.0 applies every update)
+    pub movement_sample_rate: f64,
 }
 
 impl Default for PluginConfig {
     fn default() -> Self {
         Self {
             welcome_message: "Welcome to the server!".to_string(),
+            goodbye_message: "Goodbye {player}, see you next time!".to_string(),
             max_players_tracked: 100,
             enable_notifications: true,
+            sample_interval_secs: 10,
+            concurrency_window: 60,
+            max_ingest_bytes: 64 * 1024,
+            deterministic_batch_order: true,
+            admin_players: Vec::new(),
+            allow_admin_stats_lookup: true,
+            max_sessions_per_player: 20,
+            max_session_history_players: 1000,
+            guard_duplicate_registration: true,
+            max_jump_samples: 1000,
+            emit_player_count_changes: true,
+            flush_interval_secs: 60,
+            max_flush_failures: 3,
+            movement_sample_rate: 1.0,
         }
     }
 }
@@ -47,6 +123,269 @@ pub struct PlayerData {
     pub jump_count: u32,
 }
 
+/// A completed session, from connect to disconnect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub join_time: u64,
+    pub leave_time: u64,
+}
+
+impl PlayerData {
+    /// Statistics for this player as of `now`
+    pub fn stats(&self, player_id: PlayerId, now: u64) -> PlayerStatsEvent {
+        PlayerStatsEvent {
+            player_id,
+            messages_sent: self.message_count,
+            jumps_performed: self.jump_count,
+            time_online: now.saturating_sub(self.join_time),
+        }
+    }
+
+    /// Fold another record for the same human into this one
+    pub fn merge(&mut self, other: &PlayerData) {
+        self.join_time = self.join_time.min(other.join_time);
+        if self.last_position.is_none() {
+            self.last_position = other.last_position;
+        }
+        self.message_count += other.message_count;
+        self.jump_count += other.jump_count;
+    }
+}
+
+/// Counters collected while handling events
+#[derive(Debug, Default)]
+struct PluginMetrics {
+    oversize_events: AtomicU64,
+    // Every jump applied since startup; unlike per-player counts this never goes down
+    jumps: AtomicU64,
+    // Totals across all players, for reporting only
+    movement_sampling: SamplingCounter,
+    // Per-player counters that decide which position updates are applied
+    movement_by_player: Mutex<HashMap<PlayerId, SamplingCounter>>,
+}
+
+impl PluginMetrics {
+    /// Sample a position update against the player's own history so every player
+    /// keeps `rate` of their updates, however the players are interleaved
+    fn sample_movement(&self, player_id: PlayerId, rate: f64) -> bool {
+        let processed = self
+            .movement_by_player
+            .lock()
+            .unwrap()
+            .entry(player_id)
+            .or_default()
+            .sample(rate);
+        self.movement_sampling.record(processed);
+        processed
+    }
+}
+
+/// Seen vs processed counts for an event type that is sampled
+#[derive(Debug, Default)]
+struct SamplingCounter {
+    seen: AtomicU64,
+    processed: AtomicU64,
+}
+
+impl SamplingCounter {
+    /// Count an event and decide whether to process it, keeping processed/seen at `rate`
+    fn sample(&self, rate: f64) -> bool {
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed) + 1;
+        let target = (seen as f64 * rate.clamp(0.0, 1.0)).floor() as u64;
+        self.processed
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |processed| {
+                (processed < target).then_some(processed + 1)
+            })
+            .is_ok()
+    }
+
+    /// Count an event whose sampling was decided elsewhere
+    fn record(&self, processed: bool) {
+        self.seen.fetch_add(1, Ordering::Relaxed);
+        if processed {
+            self.processed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Observed fraction of events processed (1.0 before any are seen)
+    fn effective_rate(&self) -> f64 {
+        let seen = self.seen.load(Ordering::Relaxed);
+        if seen == 0 {
+            return 1.0;
+        }
+        self.processed.load(Ordering::Relaxed) as f64 / seen as f64
+    }
+}
+
+/// Point-in-time copy of the plugin metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub oversize_events: u64,
+    /// Observed processed/seen ratio per sampled event key
+    pub effective_sample_rates: BTreeMap<String, f64>,
+}
+
+// ============================================================================
+// Persistence - Flushing player data to durable storage
+// ============================================================================
+
+/// Destination for the plugin's serialized state
+pub trait StateWriter: Send + Sync {
+    fn write_state(&self, state: &[u8]) -> std::io::Result<()>;
+}
+
+/// Writes state to a file, replacing its previous contents
+#[derive(Debug, Clone)]
+pub struct FileStateWriter {
+    pub path: PathBuf,
+}
+
+impl StateWriter for FileStateWriter {
+    fn write_state(&self, state: &[u8]) -> std::io::Result<()> {
+        std::fs::write(&self.path, state)
+    }
+}
+
+/// Flushes player data through a `StateWriter`, counting consecutive failures
+struct StatePersistence {
+    writer: Arc<dyn StateWriter>,
+    consecutive_failures: AtomicU32,
+}
+
+impl StatePersistence {
+    /// Write the current player data, entering read-only mode once failures reach the threshold
+    fn flush(
+        &self,
+        player_data: &Mutex<HashMap<PlayerId, PlayerData>>,
+        read_only: &AtomicBool,
+        max_failures: u32,
+        events: Option<&Arc<EventSystem>>,
+    ) -> Result<(), PluginError> {
+        match Self::snapshot(player_data, read_only)? {
+            Some(state) => self.record_write(self.writer.write_state(&state), read_only, max_failures, events),
+            None => Ok(()),
+        }
+    }
+
+    /// Serialize the player data, or return None while read-only
+    fn snapshot(
+        player_data: &Mutex<HashMap<PlayerId, PlayerData>>,
+        read_only: &AtomicBool,
+    ) -> Result<Option<Vec<u8>>, PluginError> {
+        if read_only.load(Ordering::Relaxed) {
+            debug!("🎯 SamplePlugin: Read-only, skipping state flush");
+            return Ok(None);
+        }
+
+        let data = player_data.lock().unwrap().clone();
+        serde_json::to_vec(&data)
+            .map(Some)
+            .map_err(|e| PluginError::ExecutionError(format!("State serialization failed: {}", e)))
+    }
+
+    /// Update the failure count from a write result
+    fn record_write(
+        &self,
+        result: std::io::Result<()>,
+        read_only: &AtomicBool,
+        max_failures: u32,
+        events: Option<&Arc<EventSystem>>,
+    ) -> Result<(), PluginError> {
+        let error = match result {
+            Ok(()) => {
+                self.consecutive_failures.store(0, Ordering::Relaxed);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        warn!("🎯 SamplePlugin: State flush failed ({} in a row): {}", failures, error);
+        if failures >= max_failures && !read_only.swap(true, Ordering::Relaxed) {
+            error!("🎯 SamplePlugin: Persistence degraded, switching to read-only tracking");
+            if let Some(events) = events {
+                emit_sample_event(events, "persistence_degraded", serde_json::json!({
+                    "consecutive_failures": failures,
+                    "error": error.to_string(),
+                    "timestamp": current_timestamp()
+                }));
+            }
+        }
+        Err(PluginError::ExecutionError(format!("State flush failed: {}", error)))
+    }
+}
+
+// ============================================================================
+// Time & Sampling - Clock abstraction and periodic samples
+// ============================================================================
+
+/// Source of the current time in seconds
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// Clock backed by the system time
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        current_timestamp()
+    }
+}
+
+/// Bounded window of sampled player counts
+#[derive(Debug)]
+struct ConcurrencyWindow {
+    samples: VecDeque<usize>,
+    capacity: usize,
+    last_sample_at: Option<u64>,
+}
+
+impl ConcurrencyWindow {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            last_sample_at: None,
+        }
+    }
+
+    /// Record a sample if at least `interval_secs` have passed since the last one
+    fn record(&mut self, now: u64, interval_secs: u64, count: usize) -> bool {
+        if let Some(last) = self.last_sample_at {
+            if now.saturating_sub(last) < interval_secs {
+                return false;
+            }
+        }
+
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(count);
+        self.last_sample_at = Some(now);
+        true
+    }
+
+    fn average(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().sum::<usize>() as f64 / self.samples.len() as f64
+    }
+}
+
+/// Record the tracked player count into `concurrency` if the sample interval has elapsed
+fn record_concurrency_sample(
+    player_data: &Mutex<HashMap<PlayerId, PlayerData>>,
+    concurrency: &Mutex<ConcurrencyWindow>,
+    clock: &dyn Clock,
+    interval_secs: u64,
+) -> bool {
+    let count = player_data.lock().unwrap().len();
+    concurrency.lock().unwrap().record(clock.now(), interval_secs, count)
+}
+
 // ============================================================================
 // Custom Events - Define your own events for inter-plugin communication
 // ============================================================================
@@ -66,6 +405,32 @@ pub struct PlayerStatsEvent {
     pub time_online: u64,
 }
 
+/// Parsed `!stats [player]` chat command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsCommand {
+    pub target: Option<String>,
+}
+
+impl StatsCommand {
+    pub fn parse(message: &str) -> Option<Self> {
+        let mut parts = message.split_whitespace();
+        if parts.next() != Some("!stats") {
+            return None;
+        }
+        Some(Self {
+            target: parts.next().map(str::to_string),
+        })
+    }
+}
+
+/// One line of a replay log: the event key as used by the event system
+/// (`core:<event>`, `client:<namespace>:<event>` or `plugin:<plugin>:<event>`) and its payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub key: String,
+    pub data: serde_json::Value,
+}
+
 // ============================================================================
 // Standard Events - Handle events from the server and other plugins
 // ============================================================================
@@ -97,13 +462,51 @@ pub struct PlayerMoveEvent {
 impl SamplePlugin {
     pub fn new() -> Self {
         info!("🎯 SamplePlugin: Creating new instance");
+        let config = PluginConfig::default();
         Self {
             name: "sample".to_string(),
             player_data: Arc::new(Mutex::new(HashMap::new())),
-            config: PluginConfig::default(),
+            concurrency: Arc::new(Mutex::new(ConcurrencyWindow::new(config.concurrency_window))),
+            account_of: Arc::new(Mutex::new(HashMap::new())),
+            session_history: Arc::new(Mutex::new(HashMap::new())),
+            jump_heights: Arc::new(Mutex::new(VecDeque::new())),
+            channel_speakers: Arc::new(Mutex::new(HashMap::new())),
+            channel_messages: Arc::new(Mutex::new(HashMap::new())),
+            metrics: Arc::new(PluginMetrics::default()),
+            events: None,
+            persistence: None,
+            read_only: Arc::new(AtomicBool::new(false)),
+            flush_task: None,
+            config,
+            clock: Arc::new(SystemClock),
+            sampler_task: None,
+            registered_events: HashSet::new(),
+            registered_on: None,
         }
     }
 
+    /// Use a custom configuration
+    pub fn with_config(mut self, config: PluginConfig) -> Self {
+        self.concurrency = Arc::new(Mutex::new(ConcurrencyWindow::new(config.concurrency_window)));
+        self.config = config;
+        self
+    }
+
+    /// Persist player data through `writer` on a timer and at shutdown
+    pub fn with_state_writer(mut self, writer: Arc<dyn StateWriter>) -> Self {
+        self.persistence = Some(Arc::new(StatePersistence {
+            writer,
+            consecutive_failures: AtomicU32::new(0),
+        }));
+        self
+    }
+
+    /// Use a custom time source (e.g. a mock clock in tests)
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Helper function to get or create player data
     fn get_or_create_player_data(&self, player_id: PlayerId) -> PlayerData {
         let mut data = self.player_data.lock().unwrap();
@@ -138,6 +541,284 @@ impl SamplePlugin {
             time_online: current_timestamp() - player_data.join_time,
         })
     }
+
+    /// Mark a group of event keys as registered; returns false if they all were already
+    fn claim_registration(&mut self, event_keys: &[&str]) -> bool {
+        if !self.config.guard_duplicate_registration {
+            return true;
+        }
+
+        let mut claimed = false;
+        for key in event_keys {
+            if self.registered_events.insert(key.to_string()) {
+                claimed = true;
+            } else {
+                warn!("🎯 SamplePlugin: Handler for {} already registered, skipping", key);
+            }
+        }
+        claimed
+    }
+
+    /// Write the tracked player data through the configured state writer, if any
+    pub fn flush_state(&self) -> Result<(), PluginError> {
+        match &self.persistence {
+            Some(persistence) => persistence.flush(
+                &self.player_data,
+                &self.read_only,
+                self.config.max_flush_failures,
+                self.events.as_ref(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether repeated persistence failures have stopped the plugin from mutating its state
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    /// Record the current player count if the sample interval has elapsed
+    pub fn sample_concurrency(&self) -> bool {
+        record_concurrency_sample(
+            &self.player_data,
+            &self.concurrency,
+            self.clock.as_ref(),
+            self.config.sample_interval_secs,
+        )
+    }
+
+    /// Merge the data of player ids that share an account into the most recent session.
+    /// Returns the number of ids that were folded into another.
+    pub fn merge_by_account(&self) -> usize {
+        if self.is_read_only() {
+            return 0;
+        }
+
+        // Lock order: `player_data` before `account_of`, matching the connect/disconnect handlers
+        let mut data = self.player_data.lock().unwrap();
+        let mut account_of = self.account_of.lock().unwrap();
+
+        let mut by_account: HashMap<&str, Vec<PlayerId>> = HashMap::new();
+        for (player_id, account) in account_of.iter() {
+            if data.contains_key(player_id) {
+                by_account.entry(account.as_str()).or_default().push(*player_id);
+            }
+        }
+
+        let previous_count = data.len();
+        let mut merged = Vec::new();
+        for ids in by_account.values().filter(|ids| ids.len() > 1) {
+            // The latest join is the live session; it absorbs the older ids
+            let survivor = *ids
+                .iter()
+                .max_by_key(|id| (data[*id].join_time, id.0))
+                .unwrap();
+            for id in ids.iter().filter(|id| **id != survivor) {
+                if let Some(old) = data.remove(id) {
+                    data.get_mut(&survivor).unwrap().merge(&old);
+                    merged.push(*id);
+                }
+            }
+        }
+
+        for id in &merged {
+            account_of.remove(id);
+        }
+        if self.config.emit_player_count_changes {
+            if let Some(events) = &self.events {
+                publish_player_count(events, previous_count, data.len(), self.clock.now());
+            }
+        }
+        merged.len()
+    }
+
+    /// Statistics for every tracked player, sorted by player id when `deterministic_batch_order` is set
+    pub fn player_stats_batch(&self) -> Vec<PlayerStatsEvent> {
+        let now = self.clock.now();
+        let data = self.player_data.lock().unwrap();
+        let mut batch: Vec<PlayerStatsEvent> = data
+            .iter()
+            .map(|(player_id, player_data)| player_data.stats(*player_id, now))
+            .collect();
+
+        if self.config.deterministic_batch_order {
+            batch.sort_by_key(|stats| stats.player_id.0);
+        }
+        batch
+    }
+
+    /// Group positioned players into clusters whose members are chained within `radius` of each other
+    pub fn player_clusters(&self, radius: f64) -> Vec<Vec<PlayerId>> {
+        let data = self.player_data.lock().unwrap();
+        let mut positioned: Vec<(PlayerId, Position)> = data
+            .iter()
+            .filter_map(|(id, player)| player.last_position.map(|pos| (*id, pos)))
+            .collect();
+        positioned.sort_by_key(|(id, _)| id.0);
+
+        // Union-find over pairwise distances
+        let mut parent: Vec<usize> = (0..positioned.len()).collect();
+        fn find(parent: &mut [usize], i: usize) -> usize {
+            let mut root = i;
+            while parent[root] != root {
+                root = parent[root];
+            }
+            parent[i] = root;
+            root
+        }
+        for i in 0..positioned.len() {
+            for j in (i + 1)..positioned.len() {
+                if distance_between(&positioned[i].1, &positioned[j].1) <= radius {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut clusters: Vec<Vec<PlayerId>> = Vec::new();
+        let mut cluster_of_root: HashMap<usize, usize> = HashMap::new();
+        for (i, (id, _)) in positioned.iter().enumerate() {
+            let root = find(&mut parent, i);
+            let index = *cluster_of_root.entry(root).or_insert_with(|| {
+                clusters.push(Vec::new());
+                clusters.len() - 1
+            });
+            clusters[index].push(*id);
+        }
+        clusters
+    }
+
+    /// Seconds since `timestamp` according to the plugin clock
+    fn elapsed_since(&self, timestamp: u64) -> u64 {
+        self.clock.now().saturating_sub(timestamp)
+    }
+
+    /// The tracked player with the longest current continuous session, and its length in seconds
+    pub fn longest_session(&self) -> Option<(PlayerId, u64)> {
+        let data = self.player_data.lock().unwrap();
+        data.iter()
+            .map(|(player_id, player)| (*player_id, self.elapsed_since(player.join_time)))
+            .max_by_key(|(player_id, elapsed)| (*elapsed, std::cmp::Reverse(player_id.0)))
+    }
+
+    /// Fraction of tracked players who joined within the last `window_secs`
+    pub fn newcomer_ratio(&self, window_secs: u64) -> f64 {
+        let now = self.clock.now();
+        let data = self.player_data.lock().unwrap();
+        if data.is_empty() {
+            return 0.0;
+        }
+
+        let newcomers = data
+            .values()
+            .filter(|player| now.saturating_sub(player.join_time) <= window_secs)
+            .count();
+        newcomers as f64 / data.len() as f64
+    }
+
+    /// Seconds `a` and `b` were online at the same time, across past and current sessions
+    pub fn session_overlap_secs(&self, a: PlayerId, b: PlayerId) -> u64 {
+        let now = self.clock.now();
+        let data = self.player_data.lock().unwrap();
+        let history = self.session_history.lock().unwrap();
+        let intervals = |player_id: PlayerId| -> Vec<(u64, u64)> {
+            let mut intervals: Vec<(u64, u64)> = history
+                .get(&player_id)
+                .map(|sessions| sessions.iter().map(|s| (s.join_time, s.leave_time)).collect())
+                .unwrap_or_default();
+            if let Some(player) = data.get(&player_id) {
+                intervals.push((player.join_time, now));
+            }
+            intervals
+        };
+
+        let b_intervals = intervals(b);
+        intervals(a)
+            .iter()
+            .flat_map(|a| b_intervals.iter().map(move |b| (a, b)))
+            .map(|(a, b)| a.1.min(b.1).saturating_sub(a.0.max(b.0)))
+            .sum()
+    }
+
+    /// Count retained jump heights per bucket of `bucket_size` meters, keyed by bucket index
+    pub fn jump_height_histogram(&self, bucket_size: f64) -> BTreeMap<u64, u32> {
+        let mut histogram = BTreeMap::new();
+        if bucket_size <= 0.0 {
+            return histogram;
+        }
+
+        for height in self.jump_heights.lock().unwrap().iter() {
+            let bucket = (height.max(0.0) / bucket_size).floor() as u64;
+            *histogram.entry(bucket).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Number of distinct players who have spoken in each channel
+    pub fn channel_unique_speakers(&self) -> HashMap<String, usize> {
+        self.channel_speakers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(channel, speakers)| (channel.clone(), speakers.len()))
+            .collect()
+    }
+
+    /// Render plugin metrics in the OpenMetrics text format, with per-channel message counts as labeled series
+    pub fn render_openmetrics(&self) -> String {
+        let players_tracked = self.player_data.lock().unwrap().len();
+        let mut channels: Vec<(String, u64)> = self
+            .channel_messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(channel, count)| (channel.clone(), *count))
+            .collect();
+        channels.sort();
+
+        let mut out = String::new();
+        out.push_str("# TYPE sample_messages counter\n");
+        out.push_str("# HELP sample_messages Chat messages seen per channel.\n");
+        for (channel, count) in &channels {
+            out.push_str(&format!(
+                "sample_messages_total{{channel=\"{}\"}} {}\n",
+                escape_label_value(channel),
+                count
+            ));
+        }
+        out.push_str("# TYPE sample_players_tracked gauge\n");
+        out.push_str(&format!("sample_players_tracked {}\n", players_tracked));
+        out.push_str("# TYPE sample_average_concurrency gauge\n");
+        out.push_str(&format!("sample_average_concurrency {}\n", self.average_concurrency()));
+        out.push_str("# TYPE sample_jumps counter\n");
+        out.push_str(&format!(
+            "sample_jumps_total {}\n",
+            self.metrics.jumps.load(Ordering::Relaxed)
+        ));
+        out.push_str("# TYPE sample_oversize_events counter\n");
+        out.push_str(&format!(
+            "sample_oversize_events_total {}\n",
+            self.metrics.oversize_events.load(Ordering::Relaxed)
+        ));
+        out.push_str("# EOF\n");
+        out
+    }
+
+    /// Current values of the plugin's counters
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            oversize_events: self.metrics.oversize_events.load(Ordering::Relaxed),
+            effective_sample_rates: BTreeMap::from([(
+                "client:movement:position_update".to_string(),
+                self.metrics.movement_sampling.effective_rate(),
+            )]),
+        }
+    }
+
+    /// Moving average of the tracked player count over the sample window
+    pub fn average_concurrency(&self) -> f64 {
+        self.concurrency.lock().unwrap().average()
+    }
 }
 
 #[async_trait]
@@ -152,133 +833,300 @@ impl SimplePlugin for SamplePlugin {
 
     async fn register_handlers(&mut self, events: Arc<EventSystem>) -> Result<(), PluginError> {
         info!("🎯 SamplePlugin: Registering event handlers...");
+        self.events = Some(Arc::clone(&events));
+
+        // Keys registered on another event system don't count for this one
+        let system = Arc::as_ptr(&events) as usize;
+        if self.registered_on != Some(system) {
+            self.registered_events.clear();
+            self.registered_on = Some(system);
+        }
+        let mut registered_groups = 0;
 
         // Clone Arc references for use in closures
         let player_data = Arc::clone(&self.player_data);
+        let account_of = Arc::clone(&self.account_of);
+        let session_history = Arc::clone(&self.session_history);
+        let metrics = Arc::clone(&self.metrics);
+        let read_only = Arc::clone(&self.read_only);
+        let clock = Arc::clone(&self.clock);
         let config = self.config.clone();
 
         // ===== CORE SERVER EVENTS =====
-        register_handlers!(events; core {
-            // Handle player connections
-            "player_connected" => move |event: serde_json::Value| {
-                info!("🎯 SamplePlugin: Player connected! {:?}", event);
+        if self.claim_registration(&["core:player_connected", "core:player_disconnected"]) {
+            registered_groups += 1;
+            register_handlers!(events; core {
+                // Handle player connections
+                "player_connected" => {
+                    let player_data = Arc::clone(&player_data);
+                    let account_of = Arc::clone(&account_of);
+                    let metrics = Arc::clone(&metrics);
+                    let read_only = Arc::clone(&read_only);
+                    let clock = Arc::clone(&clock);
+                    let config = config.clone();
+                    let events = Arc::clone(&events);
+                    move |event: serde_json::Value| {
+                        if !within_ingest_limit("core:player_connected", &event, config.max_ingest_bytes, &metrics) {
+                            return Ok(());
+                        }
+                        if read_only.load(Ordering::Relaxed) {
+                            debug!("🎯 SamplePlugin: Read-only, ignoring connection {:?}", event);
+                            return Ok(());
+                        }
+                        info!("🎯 SamplePlugin: Player connected! {:?}", event);
                 
-                if let Ok(player_id) = serde_json::from_value::<PlayerId>(event["player_id"].clone()) {
-                    // Initialize player data
-                    let mut data = player_data.lock().unwrap();
-                    data.insert(player_id, PlayerData {
-                        join_time: current_timestamp(),
-                        last_position: None,
-                        message_count: 0,
-                        jump_count: 0,
-                    });
+                        if let Ok(player_id) = serde_json::from_value::<PlayerId>(event["player_id"].clone()) {
+                            // Initialize player data
+                            let mut data = player_data.lock().unwrap();
+                            let previous_count = data.len();
+                            data.insert(player_id, PlayerData {
+                                join_time: clock.now(),
+                                last_position: None,
+                                message_count: 0,
+                                jump_count: 0,
+                            });
+                            if config.emit_player_count_changes {
+                                publish_player_count(&events, previous_count, data.len(), clock.now());
+                            }
+
+                            // Remember the account so reconnects under a new id can be merged
+                            if let Some(account) = event["account_id"].as_str() {
+                                account_of.lock().unwrap().insert(player_id, account.to_string());
+                            }
                     
-                    info!("🎯 SamplePlugin: Initialized data for player {}", player_id);
-                }
-                Ok(())
-            },
+                            info!("🎯 SamplePlugin: Initialized data for player {}", player_id);
+                        }
+                        Ok(())
+                    }
+                },
 
-            // Handle player disconnections
-            "player_disconnected" => move |event: serde_json::Value| {
-                info!("🎯 SamplePlugin: Player disconnected: {:?}", event);
+                // Handle player disconnections
+                "player_disconnected" => {
+                    let player_data = Arc::clone(&player_data);
+                    let account_of = Arc::clone(&account_of);
+                    let session_history = Arc::clone(&session_history);
+                    let clock = Arc::clone(&clock);
+                    let config = config.clone();
+                    let events = Arc::clone(&events);
+                    let metrics = Arc::clone(&metrics);
+                    let read_only = Arc::clone(&read_only);
+                    move |event: serde_json::Value| {
+                        if !within_ingest_limit("core:player_disconnected", &event, config.max_ingest_bytes, &metrics) {
+                            return Ok(());
+                        }
+                        if read_only.load(Ordering::Relaxed) {
+                            debug!("🎯 SamplePlugin: Read-only, ignoring disconnection {:?}", event);
+                            return Ok(());
+                        }
+                        info!("🎯 SamplePlugin: Player disconnected: {:?}", event);
                 
-                if let Ok(player_id) = serde_json::from_value::<PlayerId>(event["player_id"].clone()) {
-                    // Clean up player data
-                    let mut data = player_data.lock().unwrap();
-                    if let Some(player_data) = data.remove(&player_id) {
-                        let time_online = current_timestamp() - player_data.join_time;
-                        info!("🎯 SamplePlugin: Player {} was online for {}s", player_id, time_online / 1000);
+                        if let Ok(player_id) = serde_json::from_value::<PlayerId>(event["player_id"].clone()) {
+                            let mut data = player_data.lock().unwrap();
+
+                            // Say goodbye while the player is still tracked
+                            if config.enable_notifications && data.contains_key(&player_id) {
+                                emit_sample_event(&events, "send_message", serde_json::json!({
+                                    "player_id": player_id,
+                                    "message": render_player_template(&config.goodbye_message, player_id),
+                                    "timestamp": clock.now()
+                                }));
+                            }
+
+                            // Clean up player data
+                            account_of.lock().unwrap().remove(&player_id);
+                            metrics.movement_by_player.lock().unwrap().remove(&player_id);
+                            if let Some(player_data) = data.remove(&player_id) {
+                                let leave_time = clock.now();
+                                record_session(
+                                    &mut session_history.lock().unwrap(),
+                                    player_id,
+                                    SessionRecord { join_time: player_data.join_time, leave_time },
+                                    &config,
+                                );
+
+                                let time_online = leave_time.saturating_sub(player_data.join_time);
+                                info!("🎯 SamplePlugin: Player {} was online for {}s", player_id, time_online);
+
+                                if config.emit_player_count_changes {
+                                    publish_player_count(&events, data.len() + 1, data.len(), leave_time);
+                                }
+                            }
+                        }
+                        Ok(())
                     }
                 }
-                Ok(())
-            }
-        })?;
+            })?;
+        }
 
         // Clone again for client events
         let player_data = Arc::clone(&self.player_data);
+        let jump_heights = Arc::clone(&self.jump_heights);
+        let channel_speakers = Arc::clone(&self.channel_speakers);
+        let channel_messages = Arc::clone(&self.channel_messages);
+        let read_only = Arc::clone(&self.read_only);
+        let metrics = Arc::clone(&self.metrics);
+        let clock = Arc::clone(&self.clock);
         let config = self.config.clone();
 
         // ===== CLIENT EVENTS =====
-        register_handlers!(events; client {
-            // Handle chat messages
-            "chat", "message" => move |event: PlayerChatEvent| {
-                info!("🎯 SamplePlugin: Player {} said: '{}' in {}", 
-                      event.player_id, event.message, event.channel);
-
-                // Update message count
-                let mut data = player_data.lock().unwrap();
-                if let Some(player_data) = data.get_mut(&event.player_id) {
-                    player_data.message_count += 1;
-                }
+        if self.claim_registration(&["client:chat:message", "client:movement:position_update", "client:movement:jump"]) {
+            registered_groups += 1;
+            register_handlers!(events; client {
+                // Handle chat messages
+                "chat", "message" => {
+                    let player_data = Arc::clone(&player_data);
+                    let channel_speakers = Arc::clone(&channel_speakers);
+                    let channel_messages = Arc::clone(&channel_messages);
+                    let read_only = Arc::clone(&read_only);
+                    let clock = Arc::clone(&clock);
+                    let config = config.clone();
+                    let events = Arc::clone(&events);
+                    move |event: PlayerChatEvent| {
+                        info!("🎯 SamplePlugin: Player {} said: '{}' in {}", 
+                              event.player_id, event.message, event.channel);
 
-                // Respond to specific commands
-                if event.message.starts_with("!stats") {
-                    info!("🎯 SamplePlugin: Player {} requested stats", event.player_id);
-                    // Here you could emit an event to send a response back to the player
-                }
+                        // Update message count
+                        let mut data = player_data.lock().unwrap();
+                        if !read_only.load(Ordering::Relaxed) {
+                            // Channel stats only cover players we track
+                            if let Some(player_data) = data.get_mut(&event.player_id) {
+                                player_data.message_count += 1;
+                                channel_speakers
+                                    .lock()
+                                    .unwrap()
+                                    .entry(event.channel.clone())
+                                    .or_default()
+                                    .insert(event.player_id);
+                                *channel_messages
+                                    .lock()
+                                    .unwrap()
+                                    .entry(event.channel.clone())
+                                    .or_insert(0) += 1;
+                            }
+                        }
 
-                // Check for greeting
-                if event.message.to_lowercase().contains("hello") ||
-                   event.message.to_lowercase().contains("hi") {
-                    if config.enable_notifications {
-                        info!("🎯 SamplePlugin: Detected greeting from player {}", event.player_id);
+                        // Respond to specific commands
+                        if let Some(command) = StatsCommand::parse(&event.message) {
+                            info!("🎯 SamplePlugin: Player {} requested stats", event.player_id);
+                            respond_to_stats_command(&events, &config, &data, event.player_id, &command, clock.now());
+                        }
+
+                        // Check for greeting
+                        if event.message.to_lowercase().contains("hello") ||
+                           event.message.to_lowercase().contains("hi") {
+                            if config.enable_notifications {
+                                info!("🎯 SamplePlugin: Detected greeting from player {}", event.player_id);
+                            }
+                        }
+
+                        Ok(())
                     }
-                }
+                },
 
-                Ok(())
-            },
+                // Handle player movement
+                "movement", "position_update" => {
+                    let player_data = Arc::clone(&player_data);
+                    let read_only = Arc::clone(&read_only);
+                    let metrics = Arc::clone(&metrics);
+                    let sample_rate = config.movement_sample_rate;
+                    move |event: PlayerMoveEvent| {
+                        debug!("🎯 SamplePlugin: Player {} moved from {:?} to {:?}", 
+                               event.player_id, event.from_position, event.to_position);
+                        // Skip read-only drops before sampling so the effective rate only reflects sampling
+                        if read_only.load(Ordering::Relaxed) {
+                            return Ok(());
+                        }
 
-            // Handle player movement
-            "movement", "position_update" => move |event: PlayerMoveEvent| {
-                debug!("🎯 SamplePlugin: Player {} moved from {:?} to {:?}", 
-                       event.player_id, event.from_position, event.to_position);
+                        // Update last known position; untracked players aren't sampled at all
+                        let mut data = player_data.lock().unwrap();
+                        if let Some(player_data) = data.get_mut(&event.player_id) {
+                            if metrics.sample_movement(event.player_id, sample_rate) {
+                                player_data.last_position = Some(event.to_position);
+                            }
+                        }
 
-                // Update last known position
-                let mut data = player_data.lock().unwrap();
-                if let Some(player_data) = data.get_mut(&event.player_id) {
-                    player_data.last_position = Some(event.to_position);
-                }
+                        Ok(())
+                    }
+                },
 
-                Ok(())
-            },
+                // Handle jump events
+                "movement", "jump" => {
+                    let player_data = Arc::clone(&player_data);
+                    let jump_heights = Arc::clone(&jump_heights);
+                    let max_jump_samples = config.max_jump_samples;
+                    let read_only = Arc::clone(&read_only);
+                    let metrics = Arc::clone(&metrics);
+                    move |event: PlayerJumpEvent| {
+                        info!("🎯 SamplePlugin: Player {} jumped {:.1}m high! 🦘", 
+                              event.player_id, event.height);
+                        if read_only.load(Ordering::Relaxed) {
+                            return Ok(());
+                        }
 
-            // Handle jump events
-            "movement", "jump" => move |event: PlayerJumpEvent| {
-                info!("🎯 SamplePlugin: Player {} jumped {:.1}m high! 🦘", 
-                      event.player_id, event.height);
+                        // Update jump count; jumps by untracked players aren't recorded anywhere
+                        let mut data = player_data.lock().unwrap();
+                        if let Some(player_data) = data.get_mut(&event.player_id) {
+                            player_data.jump_count += 1;
+                            metrics.jumps.fetch_add(1, Ordering::Relaxed);
 
-                // Update jump count
-                let mut data = player_data.lock().unwrap();
-                if let Some(player_data) = data.get_mut(&event.player_id) {
-                    player_data.jump_count += 1;
-                }
+                            // Keep a bounded sample of heights for the histogram
+                            let mut heights = jump_heights.lock().unwrap();
+                            heights.push_back(event.height);
+                            while heights.len() > max_jump_samples {
+                                heights.pop_front();
+                            }
+                        }
+
+                        // Special handling for high jumps
+                        if event.height > 5.0 {
+                            info!("🎯 SamplePlugin: Impressive jump by player {}!", event.player_id);
+                            // Could emit a special event for high jumps
+                        }
 
-                // Special handling for high jumps
-                if event.height > 5.0 {
-                    info!("🎯 SamplePlugin: Impressive jump by player {}!", event.player_id);
-                    // Could emit a special event for high jumps
+                        Ok(())
+                    }
                 }
+            })?;
+        }
 
-                Ok(())
-            }
-        })?;
+        let metrics = Arc::clone(&self.metrics);
+        let max_ingest_bytes = self.config.max_ingest_bytes;
 
         // ===== PLUGIN EVENTS =====
-        register_handlers!(events; plugin {
-            // Listen for events from other plugins
-            "logger", "activity_logged" => |event: serde_json::Value| {
-                debug!("🎯 SamplePlugin: Logger plugin recorded: {:?}", event);
-                Ok(())
-            },
+        if self.claim_registration(&["plugin:logger:activity_logged", "plugin:inventory:item_used"]) {
+            registered_groups += 1;
+            register_handlers!(events; plugin {
+                // Listen for events from other plugins
+                "logger", "activity_logged" => {
+                    let metrics = Arc::clone(&metrics);
+                    move |event: serde_json::Value| {
+                        if !within_ingest_limit("plugin:logger:activity_logged", &event, max_ingest_bytes, &metrics) {
+                            return Ok(());
+                        }
+                        debug!("🎯 SamplePlugin: Logger plugin recorded: {:?}", event);
+                        Ok(())
+                    }
+                },
 
-            // Handle inventory events
-            "inventory", "item_used" => |event: serde_json::Value| {
-                info!("🎯 SamplePlugin: Player used item: {:?}", event);
-                Ok(())
-            }
-        })?;
+                // Handle inventory events
+                "inventory", "item_used" => {
+                    let metrics = Arc::clone(&metrics);
+                    move |event: serde_json::Value| {
+                        if !within_ingest_limit("plugin:inventory:item_used", &event, max_ingest_bytes, &metrics) {
+                            return Ok(());
+                        }
+                        info!("🎯 SamplePlugin: Player used item: {:?}", event);
+                        Ok(())
+                    }
+                }
+            })?;
+        }
 
-        info!("🎯 SamplePlugin: ✅ All handlers registered successfully!");
+        if registered_groups == 0 {
+            warn!("🎯 SamplePlugin: All handlers were already registered, nothing to do");
+        } else {
+            info!("🎯 SamplePlugin: ✅ All handlers registered successfully!");
+        }
         Ok(())
     }
 
@@ -291,6 +1139,58 @@ impl SimplePlugin for SamplePlugin {
         // Load configuration (in a real plugin, you might load from a config file)
         info!("🎯 SamplePlugin: Loaded configuration: {:?}", self.config);
 
+        // Start the background concurrency sampler (the window skips ticks until the interval elapses)
+        let player_data = Arc::clone(&self.player_data);
+        let concurrency = Arc::clone(&self.concurrency);
+        let clock = Arc::clone(&self.clock);
+        let interval_secs = self.config.sample_interval_secs;
+        // A repeated init (hot reload) must not leave the previous sampler running
+        if let Some(previous) = self.sampler_task.take() {
+            previous.abort();
+        }
+        self.sampler_task = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                record_concurrency_sample(&player_data, &concurrency, clock.as_ref(), interval_secs);
+            }
+        }));
+
+        // Periodically flush state so persistence problems surface before shutdown
+        if let Some(persistence) = &self.persistence {
+            let persistence = Arc::clone(persistence);
+            let player_data = Arc::clone(&self.player_data);
+            let read_only = Arc::clone(&self.read_only);
+            let events = self.events.clone();
+            let max_failures = self.config.max_flush_failures;
+            let interval = Duration::from_secs(self.config.flush_interval_secs.max(1));
+            if let Some(previous) = self.flush_task.take() {
+                previous.abort();
+            }
+            self.flush_task = Some(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let state = match StatePersistence::snapshot(&player_data, &read_only) {
+                        Ok(Some(state)) => state,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            error!("🎯 SamplePlugin: {}", e);
+                            continue;
+                        }
+                    };
+                    // Writers may block on disk, so keep them off the async workers
+                    let writer = Arc::clone(&persistence.writer);
+                    let result = tokio::task::spawn_blocking(move || writer.write_state(&state))
+                        .await
+                        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    // Failures are logged and counted inside record_write
+                    let _ = persistence.record_write(result, &read_only, max_failures, events.as_ref());
+                }
+            }));
+        }
+
         // Announce our startup to other plugins
         let events = context.events();
         events
@@ -331,6 +1231,16 @@ impl SimplePlugin for SamplePlugin {
     }
 
     async fn on_shutdown(&mut self, context: Arc<dyn ServerContext>) -> Result<(), PluginError> {
+        if let Some(sampler) = self.sampler_task.take() {
+            sampler.abort();
+        }
+        if let Some(flusher) = self.flush_task.take() {
+            flusher.abort();
+        }
+        if let Err(e) = self.flush_state() {
+            error!("🎯 SamplePlugin: Final state flush failed: {}", e);
+        }
+
         let player_count = self.player_data.lock().unwrap().len();
         
         context.log(
@@ -342,9 +1252,12 @@ impl SimplePlugin for SamplePlugin {
         );
 
         // Generate final statistics
-        let data = self.player_data.lock().unwrap();
-        let total_messages: u32 = data.values().map(|p| p.message_count).sum();
-        let total_jumps: u32 = data.values().map(|p| p.jump_count).sum();
+        let (total_messages, total_jumps) = {
+            let data = self.player_data.lock().unwrap();
+            let total_messages: u32 = data.values().map(|p| p.message_count).sum();
+            let total_jumps: u32 = data.values().map(|p| p.jump_count).sum();
+            (total_messages, total_jumps)
+        };
 
         info!("🎯 SamplePlugin: Session stats - Messages: {}, Jumps: {}", 
               total_messages, total_jumps);
@@ -389,6 +1302,194 @@ pub fn distance_between(pos1: &Position, pos2: &Position) -> f64 {
     (dx * dx + dy * dy + dz * dz).sqrt()
 }
 
+/// Replace `{player}` in a message template with the player's id
+pub fn render_player_template(template: &str, player_id: PlayerId) -> String {
+    template.replace("{player}", &player_id.to_string())
+}
+
+/// Append a completed session, bounding both sessions per player and the number of players kept.
+/// Reconnects may arrive under fresh ids, so without the player bound the history grows forever.
+fn record_session(
+    history: &mut HashMap<PlayerId, VecDeque<SessionRecord>>,
+    player_id: PlayerId,
+    session: SessionRecord,
+    config: &PluginConfig,
+) {
+    let sessions = history.entry(player_id).or_default();
+    sessions.push_back(session);
+    while sessions.len() > config.max_sessions_per_player {
+        sessions.pop_front();
+    }
+
+    while history.len() > config.max_session_history_players {
+        let stalest = history
+            .iter()
+            .min_by_key(|(_, sessions)| sessions.back().map_or(0, |s| s.leave_time))
+            .map(|(id, _)| *id);
+        match stalest {
+            Some(id) => history.remove(&id),
+            None => break,
+        };
+    }
+}
+
+/// Check a raw JSON payload against the ingest limit, logging and counting oversize events
+fn within_ingest_limit(
+    event_key: &str,
+    event: &serde_json::Value,
+    max_bytes: usize,
+    metrics: &PluginMetrics,
+) -> bool {
+    let size = serde_json::to_vec(event).map_or(usize::MAX, |bytes| bytes.len());
+    if size <= max_bytes {
+        return true;
+    }
+
+    metrics.oversize_events.fetch_add(1, Ordering::Relaxed);
+    warn!(
+        "🎯 SamplePlugin: sample::oversize_event - rejected {} ({} bytes, limit {})",
+        event_key, size, max_bytes
+    );
+    false
+}
+
+/// Answer a `!stats` command with `sample::player_stats`, or a `sample::send_message` explaining why not
+fn respond_to_stats_command(
+    events: &Arc<EventSystem>,
+    config: &PluginConfig,
+    data: &HashMap<PlayerId, PlayerData>,
+    requester: PlayerId,
+    command: &StatsCommand,
+    now: u64,
+) {
+    let reply = |message: String| {
+        emit_sample_event(events, "send_message", serde_json::json!({
+            "player_id": requester,
+            "message": message,
+            "timestamp": now
+        }));
+    };
+
+    let target = match &command.target {
+        Some(target) if config.allow_admin_stats_lookup => {
+            let target = match PlayerId::from_str(target) {
+                Ok(target) => target,
+                Err(_) => {
+                    reply(format!("'{}' is not a valid player id", target));
+                    return;
+                }
+            };
+            // Looking yourself up by id is still a self-lookup
+            if target != requester && !config.admin_players.contains(&requester) {
+                warn!("🎯 SamplePlugin: Non-admin {} tried to view stats for {}", requester, target);
+                reply("Only admins can view other players' stats".to_string());
+                return;
+            }
+            target
+        }
+        _ => requester,
+    };
+
+    match data.get(&target) {
+        Some(player_data) => emit_sample_event(events, "player_stats", serde_json::json!({
+            "requester": requester,
+            "stats": player_data.stats(target, now)
+        })),
+        None => reply(format!("No stats found for player {}", target)),
+    }
+}
+
+/// Emit `sample::player_count_changed` if the tracked player count moved
+fn publish_player_count(events: &Arc<EventSystem>, previous: usize, count: usize, now: u64) {
+    if count == previous {
+        return;
+    }
+    emit_sample_event(events, "player_count_changed", serde_json::json!({
+        "count": count,
+        "delta": count as i64 - previous as i64,
+        "timestamp": now
+    }));
+}
+
+/// Emit a `sample` plugin event from a synchronous handler without blocking it
+fn emit_sample_event(events: &Arc<EventSystem>, event_name: &'static str, payload: serde_json::Value) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        warn!("🎯 SamplePlugin: No async runtime, dropping {} event", event_name);
+        return;
+    };
+
+    let events = Arc::clone(events);
+    runtime.spawn(async move {
+        if let Err(e) = events.emit_plugin("sample", event_name, &payload).await {
+            error!("🎯 SamplePlugin: Failed to emit {}: {}", event_name, e);
+        }
+    });
+}
+
+/// Replay a JSON-lines log of `RecordedEvent`s into `events`, emitting at most
+/// `max_events_per_sec` per second (`None` replays as fast as possible; `Some(0)`
+/// is rejected). Returns the number of events replayed.
+pub async fn replay_from_file(
+    events: &EventSystem,
+    path: impl AsRef<Path>,
+    max_events_per_sec: Option<u32>,
+) -> Result<usize, PluginError> {
+    if max_events_per_sec == Some(0) {
+        return Err(PluginError::ExecutionError(
+            "Replay rate must be at least one event per second".to_string(),
+        ));
+    }
+    let path = path.as_ref();
+    let log = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| PluginError::ExecutionError(format!("Failed to read {}: {}", path.display(), e)))?;
+
+    let mut replayed = 0;
+    for (line_number, line) in log.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let recorded: RecordedEvent = serde_json::from_str(line).map_err(|e| {
+            PluginError::ExecutionError(format!("Bad event on line {}: {}", line_number + 1, e))
+        })?;
+
+        // Pace in one-second batches of `max_events_per_sec`
+        if let Some(rate) = max_events_per_sec {
+            if replayed > 0 && replayed % rate as usize == 0 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+
+        let parts: Vec<&str> = recorded.key.splitn(3, ':').collect();
+        let result = match parts.as_slice() {
+            ["core", event_name] => events.emit_core(event_name, &recorded.data).await,
+            ["client", namespace, event_name] => {
+                events.emit_client(namespace, event_name, &recorded.data).await
+            }
+            ["plugin", plugin_name, event_name] => {
+                events.emit_plugin(plugin_name, event_name, &recorded.data).await
+            }
+            _ => {
+                return Err(PluginError::ExecutionError(format!(
+                    "Unknown event key '{}' on line {}",
+                    recorded.key,
+                    line_number + 1
+                )))
+            }
+        };
+        result.map_err(|e| PluginError::ExecutionError(e.to_string()))?;
+        replayed += 1;
+    }
+
+    info!("🎯 SamplePlugin: Replayed {} events from {}", replayed, path.display());
+    Ok(replayed)
+}
+
+/// Escape a label value for the OpenMetrics text format
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 /// Format time duration in a human-readable way
 pub fn format_duration(seconds: u64) -> String {
     let hours = seconds / 3600;
@@ -412,6 +1513,56 @@ pub fn format_duration(seconds: u64) -> String {
 mod tests {
     use super::*;
 
+    /// Manually advanced clock for time-based tests
+    struct MockClock(AtomicU64);
+
+    impl MockClock {
+        fn new(start: u64) -> Self {
+            Self(AtomicU64::new(start))
+        }
+
+        fn advance(&self, secs: u64) {
+            self.0.fetch_add(secs, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    /// Collect every `sample::<event_name>` payload emitted on `events`
+    async fn capture_sample_events(
+        events: &EventSystem,
+        event_name: &str,
+    ) -> Arc<Mutex<Vec<serde_json::Value>>> {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&captured);
+        events
+            .on_plugin("sample", event_name, move |event: serde_json::Value| {
+                sink.lock().unwrap().push(event);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        captured
+    }
+
+    /// Let spawned emits run to completion
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    fn test_player(join_time: u64) -> PlayerData {
+        PlayerData {
+            join_time,
+            last_position: None,
+            message_count: 0,
+            jump_count: 0,
+        }
+    }
+
     #[test]
     fn test_distance_calculation() {
         let pos1 = Position { x: 0.0, y: 0.0, z: 0.0 };
@@ -433,4 +1584,613 @@ mod tests {
         assert_eq!(plugin.name(), "sample");
         assert_eq!(plugin.version(), "1.0.0");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_average_concurrency_with_mock_clock() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let plugin = SamplePlugin::new().with_clock(clock.clone());
+        let interval = plugin.config.sample_interval_secs;
+        assert_eq!(plugin.average_concurrency(), 0.0);
+
+        let ids: Vec<PlayerId> = (0..4).map(|_| PlayerId::new()).collect();
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            for id in &ids[..2] {
+                data.insert(*id, test_player(1_000));
+            }
+        }
+        assert!(plugin.sample_concurrency());
+        // Too soon for another sample
+        assert!(!plugin.sample_concurrency());
+
+        clock.advance(interval);
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            for id in &ids[2..] {
+                data.insert(*id, test_player(1_000));
+            }
+        }
+        assert!(plugin.sample_concurrency());
+
+        clock.advance(interval);
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            for id in &ids[..3] {
+                data.remove(id);
+            }
+        }
+        assert!(plugin.sample_concurrency());
+
+        // Samples were 2, 4 and 1
+        assert!((plugin.average_concurrency() - 7.0 / 3.0).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_goodbye_message_on_disconnect() {
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new();
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        let sent = capture_sample_events(&events, "send_message").await;
+
+        let player_id = PlayerId::new();
+        let event = serde_json::json!({ "player_id": player_id });
+        events.emit_core("player_connected", &event).await.unwrap();
+        events.emit_core("player_disconnected", &event).await.unwrap();
+        settle().await;
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["player_id"], serde_json::json!(player_id));
+        assert_eq!(
+            sent[0]["message"],
+            format!("Goodbye {}, see you next time!", player_id)
+        );
+        assert!(plugin.player_data.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_merge_by_account() {
+        let plugin = SamplePlugin::new();
+        let old_session = PlayerId::new();
+        let new_session = PlayerId::new();
+        let other = PlayerId::new();
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            data.insert(
+                old_session,
+                PlayerData { message_count: 3, jump_count: 1, ..test_player(100) },
+            );
+            data.insert(
+                new_session,
+                PlayerData { message_count: 2, jump_count: 4, ..test_player(200) },
+            );
+            data.insert(other, PlayerData { message_count: 7, ..test_player(150) });
+
+            let mut accounts = plugin.account_of.lock().unwrap();
+            accounts.insert(old_session, "alice".to_string());
+            accounts.insert(new_session, "alice".to_string());
+            accounts.insert(other, "bob".to_string());
+        }
+
+        assert_eq!(plugin.merge_by_account(), 1);
+
+        let data = plugin.player_data.lock().unwrap();
+        assert_eq!(data.len(), 2);
+        assert!(!data.contains_key(&old_session));
+        let merged = &data[&new_session];
+        assert_eq!(merged.message_count, 5);
+        assert_eq!(merged.jump_count, 5);
+        assert_eq!(merged.join_time, 100);
+        assert_eq!(data[&other].message_count, 7);
+    }
+
+    #[tokio::test]
+    async fn test_oversize_payload_rejected() {
+        let events = Arc::new(EventSystem::new());
+        let config = PluginConfig { max_ingest_bytes: 256, ..PluginConfig::default() };
+        let mut plugin = SamplePlugin::new().with_config(config);
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        let oversize = PlayerId::new();
+        let payload = serde_json::json!({ "player_id": oversize, "padding": "x".repeat(1024) });
+        events.emit_core("player_connected", &payload).await.unwrap();
+
+        let normal = PlayerId::new();
+        let payload = serde_json::json!({ "player_id": normal });
+        events.emit_core("player_connected", &payload).await.unwrap();
+
+        let data = plugin.player_data.lock().unwrap();
+        assert!(!data.contains_key(&oversize));
+        assert!(data.contains_key(&normal));
+        assert_eq!(plugin.metrics_snapshot().oversize_events, 1);
+    }
+
+    #[test]
+    fn test_player_clusters() {
+        let plugin = SamplePlugin::new();
+        let (near_a, near_b, far) = (PlayerId::new(), PlayerId::new(), PlayerId::new());
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            let at = |x: f64| PlayerData {
+                last_position: Some(Position { x, y: 0.0, z: 0.0 }),
+                ..test_player(0)
+            };
+            data.insert(near_a, at(0.0));
+            data.insert(near_b, at(3.0));
+            data.insert(far, at(100.0));
+            // Players without a position are ignored
+            data.insert(PlayerId::new(), test_player(0));
+        }
+
+        let mut clusters = plugin.player_clusters(5.0);
+        clusters.sort_by_key(|cluster| cluster.len());
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0], vec![far]);
+        assert_eq!(clusters[1].len(), 2);
+        assert!(clusters[1].contains(&near_a) && clusters[1].contains(&near_b));
+    }
+
+    #[test]
+    fn test_stats_batch_is_deterministic() {
+        let players: Vec<(PlayerId, PlayerData)> = (0..16)
+            .map(|i| (PlayerId::new(), PlayerData { message_count: i, ..test_player(500) }))
+            .collect();
+        let render = |players: &[(PlayerId, PlayerData)]| {
+            let plugin = SamplePlugin::new().with_clock(Arc::new(MockClock::new(1_000)));
+            plugin.player_data.lock().unwrap().extend(players.iter().cloned());
+            serde_json::to_vec(&plugin.player_stats_batch()).unwrap()
+        };
+
+        let mut reversed = players.clone();
+        reversed.reverse();
+        assert_eq!(render(&players), render(&reversed));
+    }
+
+    #[test]
+    fn test_newcomer_ratio() {
+        let plugin = SamplePlugin::new().with_clock(Arc::new(MockClock::new(10_000)));
+        assert_eq!(plugin.newcomer_ratio(300), 0.0);
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            data.insert(PlayerId::new(), test_player(1_000));
+            data.insert(PlayerId::new(), test_player(9_000));
+            data.insert(PlayerId::new(), test_player(9_800));
+            data.insert(PlayerId::new(), test_player(9_990));
+        }
+
+        assert_eq!(plugin.newcomer_ratio(300), 0.5);
+        assert_eq!(plugin.newcomer_ratio(1_000), 0.75);
+    }
+
+    /// Register a plugin with `admin` configured as an admin and connect the given players
+    async fn stats_command_fixture(
+        admin: PlayerId,
+        players: &[PlayerId],
+    ) -> (Arc<EventSystem>, SamplePlugin) {
+        let events = Arc::new(EventSystem::new());
+        let config = PluginConfig { admin_players: vec![admin], ..PluginConfig::default() };
+        let mut plugin = SamplePlugin::new().with_config(config);
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        for player_id in players {
+            let event = serde_json::json!({ "player_id": player_id });
+            events.emit_core("player_connected", &event).await.unwrap();
+        }
+        (events, plugin)
+    }
+
+    async fn send_chat(events: &EventSystem, player_id: PlayerId, message: &str) {
+        let event = PlayerChatEvent {
+            player_id,
+            message: message.to_string(),
+            channel: "global".to_string(),
+            timestamp: current_timestamp(),
+        };
+        events.emit_client("chat", "message", &event).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stats_command_self_lookup() {
+        let player = PlayerId::new();
+        let (events, _plugin) = stats_command_fixture(PlayerId::new(), &[player]).await;
+        let stats = capture_sample_events(&events, "player_stats").await;
+
+        send_chat(&events, player, "!stats").await;
+        // A non-admin naming themselves is also a self-lookup
+        send_chat(&events, player, &format!("!stats {}", player)).await;
+        settle().await;
+
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0]["stats"]["player_id"], serde_json::json!(player));
+        assert_eq!(stats[0]["stats"]["messages_sent"], 1);
+        assert_eq!(stats[1]["stats"]["player_id"], serde_json::json!(player));
+    }
+
+    #[tokio::test]
+    async fn test_stats_command_admin_lookup() {
+        let (admin, target) = (PlayerId::new(), PlayerId::new());
+        let (events, _plugin) = stats_command_fixture(admin, &[admin, target]).await;
+        let stats = capture_sample_events(&events, "player_stats").await;
+
+        send_chat(&events, admin, &format!("!stats {}", target)).await;
+        settle().await;
+
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0]["requester"], serde_json::json!(admin));
+        assert_eq!(stats[0]["stats"]["player_id"], serde_json::json!(target));
+    }
+
+    #[tokio::test]
+    async fn test_stats_command_non_admin_denied() {
+        let (player, target) = (PlayerId::new(), PlayerId::new());
+        let (events, _plugin) = stats_command_fixture(PlayerId::new(), &[player, target]).await;
+        let stats = capture_sample_events(&events, "player_stats").await;
+        let messages = capture_sample_events(&events, "send_message").await;
+
+        send_chat(&events, player, &format!("!stats {}", target)).await;
+        settle().await;
+
+        assert!(stats.lock().unwrap().is_empty());
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["player_id"], serde_json::json!(player));
+    }
+
+    #[test]
+    fn test_session_overlap_secs() {
+        let plugin = SamplePlugin::new().with_clock(Arc::new(MockClock::new(1_000)));
+        let (a, b) = (PlayerId::new(), PlayerId::new());
+        {
+            // a played 100..400 and is back online since 900; b has been online since 300
+            let mut history = plugin.session_history.lock().unwrap();
+            history.entry(a).or_default().push_back(SessionRecord { join_time: 100, leave_time: 400 });
+            let mut data = plugin.player_data.lock().unwrap();
+            data.insert(a, test_player(900));
+            data.insert(b, test_player(300));
+        }
+
+        assert_eq!(plugin.session_overlap_secs(a, b), 200);
+        assert_eq!(plugin.session_overlap_secs(b, a), 200);
+        assert_eq!(plugin.session_overlap_secs(a, PlayerId::new()), 0);
+    }
+
+    #[tokio::test]
+    async fn test_register_handlers_is_idempotent() {
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new();
+
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        let registered = events.get_stats().await.total_handlers;
+        assert_eq!(registered, 7);
+
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        assert_eq!(events.get_stats().await.total_handlers, registered);
+
+        // Each event is still handled exactly once
+        let player_id = PlayerId::new();
+        let event = serde_json::json!({ "player_id": player_id });
+        events.emit_core("player_connected", &event).await.unwrap();
+        send_chat(&events, player_id, "hello").await;
+        assert_eq!(plugin.player_data.lock().unwrap()[&player_id].message_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_jump_height_histogram() {
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new();
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        let player_id = PlayerId::new();
+        let event = serde_json::json!({ "player_id": player_id });
+        events.emit_core("player_connected", &event).await.unwrap();
+        for height in [0.2, 0.9, 1.0, 1.5, 3.7] {
+            let event = PlayerJumpEvent {
+                player_id,
+                height,
+                position: Position { x: 0.0, y: 0.0, z: 0.0 },
+                timestamp: current_timestamp(),
+            };
+            events.emit_client("movement", "jump", &event).await.unwrap();
+        }
+
+        // Jumps also feed the monotonic sample_jumps_total counter
+        assert!(plugin.render_openmetrics().contains("sample_jumps_total 5\n"));
+
+        let histogram = plugin.jump_height_histogram(1.0);
+        assert_eq!(histogram, BTreeMap::from([(0, 2), (1, 2), (3, 1)]));
+        assert!(plugin.jump_height_histogram(0.0).is_empty());
+
+        // Jumps by players we don't track are ignored
+        let event = PlayerJumpEvent {
+            player_id: PlayerId::new(),
+            height: 9.0,
+            position: Position { x: 0.0, y: 0.0, z: 0.0 },
+            timestamp: current_timestamp(),
+        };
+        events.emit_client("movement", "jump", &event).await.unwrap();
+        assert_eq!(plugin.jump_height_histogram(1.0), histogram);
+        assert!(plugin.render_openmetrics().contains("sample_jumps_total 5\n"));
+    }
+
+    #[tokio::test]
+    async fn test_player_count_changed_events() {
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new();
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        let changes = capture_sample_events(&events, "player_count_changed").await;
+
+        let event = serde_json::json!({ "player_id": PlayerId::new() });
+        events.emit_core("player_connected", &event).await.unwrap();
+        settle().await;
+        events.emit_core("player_disconnected", &event).await.unwrap();
+        settle().await;
+
+        let changes = changes.lock().unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!((&changes[0]["count"], &changes[0]["delta"]), (&1.into(), &1.into()));
+        assert_eq!((&changes[1]["count"], &changes[1]["delta"]), (&0.into(), &(-1).into()));
+    }
+
+    #[tokio::test]
+    async fn test_channel_unique_speakers() {
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new();
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        let (alice, bob) = (PlayerId::new(), PlayerId::new());
+        for player_id in [alice, bob] {
+            let event = serde_json::json!({ "player_id": player_id });
+            events.emit_core("player_connected", &event).await.unwrap();
+        }
+        // The last message comes from a player we never saw connect
+        let stranger = PlayerId::new();
+        for (player_id, channel) in [(alice, "global"), (alice, "global"), (bob, "global"), (bob, "trade"), (stranger, "lfg")] {
+            let event = PlayerChatEvent {
+                player_id,
+                message: "lf group".to_string(),
+                channel: channel.to_string(),
+                timestamp: current_timestamp(),
+            };
+            events.emit_client("chat", "message", &event).await.unwrap();
+        }
+
+        let speakers = plugin.channel_unique_speakers();
+        assert_eq!(speakers.len(), 2);
+        assert_eq!(speakers["global"], 2);
+        assert_eq!(speakers["trade"], 1);
+        assert!(!plugin.channel_messages.lock().unwrap().contains_key("lfg"));
+    }
+
+    /// Writer that always fails, as if the disk were full
+    #[derive(Default)]
+    struct FailingWriter {
+        attempts: AtomicU32,
+    }
+
+    impl StateWriter for FailingWriter {
+        fn write_state(&self, _state: &[u8]) -> std::io::Result<()> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            Err(std::io::Error::other("no space left on device"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_persistence_degrades_to_read_only() {
+        let events = Arc::new(EventSystem::new());
+        let writer = Arc::new(FailingWriter::default());
+        let config = PluginConfig { max_flush_failures: 3, ..PluginConfig::default() };
+        let mut plugin = SamplePlugin::new()
+            .with_config(config)
+            .with_state_writer(writer.clone());
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        let degraded = capture_sample_events(&events, "persistence_degraded").await;
+
+        for _ in 0..2 {
+            assert!(plugin.flush_state().is_err());
+        }
+        assert!(!plugin.is_read_only());
+        assert!(plugin.flush_state().is_err());
+        assert!(plugin.is_read_only());
+
+        // Read-only mode stops writes and state mutation
+        assert!(plugin.flush_state().is_ok());
+        assert_eq!(writer.attempts.load(Ordering::SeqCst), 3);
+        let event = serde_json::json!({ "player_id": PlayerId::new() });
+        events.emit_core("player_connected", &event).await.unwrap();
+        settle().await;
+
+        assert!(plugin.player_data.lock().unwrap().is_empty());
+        let degraded = degraded.lock().unwrap();
+        assert_eq!(degraded.len(), 1);
+        assert_eq!(degraded[0]["consecutive_failures"], 3);
+    }
+
+    #[test]
+    fn test_longest_session() {
+        let plugin = SamplePlugin::new().with_clock(Arc::new(MockClock::new(5_000)));
+        assert_eq!(plugin.longest_session(), None);
+
+        let veteran = PlayerId::new();
+        {
+            let mut data = plugin.player_data.lock().unwrap();
+            data.insert(PlayerId::new(), test_player(4_000));
+            data.insert(veteran, test_player(1_000));
+            data.insert(PlayerId::new(), test_player(4_900));
+        }
+
+        assert_eq!(plugin.longest_session(), Some((veteran, 4_000)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_replay_from_file_is_rate_limited() {
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new();
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        let log: String = (0..3)
+            .map(|_| {
+                let recorded = RecordedEvent {
+                    key: "core:player_connected".to_string(),
+                    data: serde_json::json!({ "player_id": PlayerId::new() }),
+                };
+                serde_json::to_string(&recorded).unwrap() + "\n"
+            })
+            .collect();
+        let path = std::env::temp_dir().join(format!("sample_replay_{}.jsonl", PlayerId::new()));
+        std::fs::write(&path, log).unwrap();
+
+        // 3 events at 2/s need a second batch one second after the first
+        let started = tokio::time::Instant::now();
+        let replayed = replay_from_file(&events, &path, Some(2)).await;
+        let elapsed = started.elapsed();
+        assert!(replay_from_file(&events, &path, Some(0)).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replayed.unwrap(), 3);
+        assert!(elapsed >= Duration::from_secs(1));
+        assert_eq!(plugin.player_data.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_render_openmetrics_labels_channels() {
+        let plugin = SamplePlugin::new();
+        plugin.player_data.lock().unwrap().insert(PlayerId::new(), test_player(0));
+        {
+            let mut channels = plugin.channel_messages.lock().unwrap();
+            channels.insert("global".to_string(), 3);
+            channels.insert("team \"red\"".to_string(), 1);
+        }
+
+        let rendered = plugin.render_openmetrics();
+        assert!(rendered.contains("sample_messages_total{channel=\"global\"} 3\n"));
+        assert!(rendered.contains("sample_messages_total{channel=\"team \\\"red\\\"\"} 1\n"));
+        assert!(rendered.contains("sample_players_tracked 1\n"));
+        assert!(rendered.contains("sample_jumps_total 0\n"));
+        assert!(rendered.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn test_movement_sampling_effective_rate() {
+        let events = Arc::new(EventSystem::new());
+        let config = PluginConfig { movement_sample_rate: 0.25, ..PluginConfig::default() };
+        let mut plugin = SamplePlugin::new().with_config(config);
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+        assert_eq!(
+            plugin.metrics_snapshot().effective_sample_rates["client:movement:position_update"],
+            1.0
+        );
+
+        let player_id = PlayerId::new();
+        let event = serde_json::json!({ "player_id": player_id });
+        events.emit_core("player_connected", &event).await.unwrap();
+
+        let origin = Position { x: 0.0, y: 0.0, z: 0.0 };
+        for i in 0..1000 {
+            let event = PlayerMoveEvent {
+                player_id,
+                from_position: origin,
+                to_position: Position { x: i as f64, ..origin },
+                speed: 1.0,
+            };
+            events.emit_client("movement", "position_update", &event).await.unwrap();
+        }
+
+        let rate = plugin.metrics_snapshot().effective_sample_rates["client:movement:position_update"];
+        assert!((rate - 0.25).abs() < 0.01, "effective rate was {}", rate);
+    }
+
+    #[tokio::test]
+    async fn test_movement_sampling_is_per_player() {
+        let events = Arc::new(EventSystem::new());
+        let config = PluginConfig { movement_sample_rate: 0.25, ..PluginConfig::default() };
+        let mut plugin = SamplePlugin::new().with_config(config);
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        let players: Vec<PlayerId> = (0..4).map(|_| PlayerId::new()).collect();
+        for player_id in &players {
+            let event = serde_json::json!({ "player_id": player_id });
+            events.emit_core("player_connected", &event).await.unwrap();
+        }
+
+        // Round-robin updates would all land on one player with a single shared counter
+        let origin = Position { x: 0.0, y: 0.0, z: 0.0 };
+        for round in 0..100 {
+            for player_id in &players {
+                let event = PlayerMoveEvent {
+                    player_id: *player_id,
+                    from_position: origin,
+                    to_position: Position { x: round as f64, ..origin },
+                    speed: 1.0,
+                };
+                events.emit_client("movement", "position_update", &event).await.unwrap();
+            }
+        }
+
+        let counters = plugin.metrics.movement_by_player.lock().unwrap();
+        for player_id in &players {
+            let rate = counters[player_id].effective_rate();
+            assert!((rate - 0.25).abs() < 0.01, "player {} effective rate was {}", player_id, rate);
+            assert!(plugin.player_data.lock().unwrap()[player_id].last_position.is_some());
+        }
+        let rate = plugin.metrics_snapshot().effective_sample_rates["client:movement:position_update"];
+        assert!((rate - 0.25).abs() < 0.01, "effective rate was {}", rate);
+    }
+
+    #[tokio::test]
+    async fn test_session_history_uses_plugin_clock() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let events = Arc::new(EventSystem::new());
+        let mut plugin = SamplePlugin::new().with_clock(clock.clone());
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        let player_id = PlayerId::new();
+        let event = serde_json::json!({ "player_id": player_id });
+        events.emit_core("player_connected", &event).await.unwrap();
+        clock.advance(60);
+        events.emit_core("player_disconnected", &event).await.unwrap();
+
+        let history = plugin.session_history.lock().unwrap();
+        let sessions: Vec<SessionRecord> = history[&player_id].iter().copied().collect();
+        assert_eq!(sessions, vec![SessionRecord { join_time: 1_000, leave_time: 1_060 }]);
+    }
+
+    #[tokio::test]
+    async fn test_session_history_evicts_stalest_players() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let events = Arc::new(EventSystem::new());
+        let config = PluginConfig { max_session_history_players: 2, ..PluginConfig::default() };
+        let mut plugin = SamplePlugin::new().with_config(config).with_clock(clock.clone());
+        plugin.register_handlers(Arc::clone(&events)).await.unwrap();
+
+        // Each reconnect arrives under a fresh id
+        let ids: Vec<PlayerId> = (0..3).map(|_| PlayerId::new()).collect();
+        for player_id in &ids {
+            let event = serde_json::json!({ "player_id": player_id });
+            events.emit_core("player_connected", &event).await.unwrap();
+            clock.advance(10);
+            events.emit_core("player_disconnected", &event).await.unwrap();
+        }
+
+        let history = plugin.session_history.lock().unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(!history.contains_key(&ids[0]));
+        assert!(history.contains_key(&ids[1]) && history.contains_key(&ids[2]));
+    }
+
+    #[tokio::test]
+    async fn test_register_handlers_on_new_event_system() {
+        let (first, second) = (Arc::new(EventSystem::new()), Arc::new(EventSystem::new()));
+        let mut plugin = SamplePlugin::new();
+
+        plugin.register_handlers(Arc::clone(&first)).await.unwrap();
+        plugin.register_handlers(Arc::clone(&second)).await.unwrap();
+        assert_eq!(first.get_stats().await.total_handlers, 7);
+        assert_eq!(second.get_stats().await.total_handlers, 7);
+
+        let player_id = PlayerId::new();
+        let event = serde_json::json!({ "player_id": player_id });
+        second.emit_core("player_connected", &event).await.unwrap();
+        assert!(plugin.player_data.lock().unwrap().contains_key(&player_id));
+    }
+}